@@ -2,13 +2,13 @@ use crate::data::eval::{EvalError, ExprEvalContext, RowEvalContext};
 use crate::data::expr::Expr;
 use crate::data::op::Op;
 use crate::data::value::Value;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::result;
 use std::sync::Arc;
 
 type Result<T> = result::Result<T, EvalError>;
 
-pub(crate) struct OpCond;
-
 pub(crate) struct OpCoalesce;
 
 impl Op for OpCoalesce {
@@ -49,6 +49,7 @@ pub(crate) fn row_eval_coalesce<'a, T: RowEvalContext + 'a>(
 }
 
 const IF_NAME: &str = "if";
+const COND_NAME: &str = "cond";
 
 pub(crate) fn partial_eval_coalesce<'a, T: ExprEvalContext + 'a>(
     ctx: &'a T,
@@ -133,6 +134,76 @@ pub(crate) fn row_eval_switch_expr<'a, T: RowEvalContext + 'a>(
     default.row_eval(ctx)
 }
 
+/// Key wrapper used for the O(1) dispatch table built by `partial_eval_switch_expr`.
+/// `Value` does not have a blanket `Hash` impl (floats and aggregates cannot be
+/// hashed in a way that respects `Eq`), so only the variants that are safe to use
+/// as hash keys are ever wrapped in this type; see `as_hash_key`.
+#[derive(Clone, PartialEq)]
+pub(crate) struct HashableConst<'a>(Value<'a>);
+
+impl<'a> Eq for HashableConst<'a> {}
+
+impl<'a> Hash for HashableConst<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            Value::Null => 0u8.hash(state),
+            Value::Bool(b) => {
+                1u8.hash(state);
+                b.hash(state);
+            }
+            Value::Int(i) => {
+                2u8.hash(state);
+                i.hash(state);
+            }
+            Value::Str(s) => {
+                3u8.hash(state);
+                s.hash(state);
+            }
+            Value::Bytes(b) => {
+                4u8.hash(state);
+                b.hash(state);
+            }
+            Value::Float(f) => {
+                5u8.hash(state);
+                // Normalize -0.0 to 0.0 so that values considered equal under
+                // `Value`'s `==` (which uses plain IEEE comparison) also hash
+                // to the same bucket.
+                let f = if *f == 0.0 { 0.0 } else { *f };
+                f.to_bits().hash(state);
+            }
+            v => unreachable!("{:?} is not a valid switch hash key", v),
+        }
+    }
+}
+
+/// Returns `Some` when `v` can be used as a switch dispatch hash key, i.e. it is a
+/// variant with a total, `Eq`-consistent `Hash`. NaN floats and aggregate values
+/// (lists, maps, ...) return `None`, forcing the caller to fall back to the linear
+/// scan in `row_eval_switch_expr`.
+fn as_hash_key<'a>(v: &Value<'a>) -> Option<HashableConst<'a>> {
+    match v {
+        Value::Null | Value::Bool(_) | Value::Int(_) | Value::Str(_) | Value::Bytes(_) => {
+            Some(HashableConst(v.clone()))
+        }
+        Value::Float(f) if !f.is_nan() => Some(HashableConst(v.clone())),
+        _ => None,
+    }
+}
+
+pub(crate) fn row_eval_switch_expr_hash<'a, T: RowEvalContext + 'a>(
+    ctx: &'a T,
+    expr: &'a Expr<'a>,
+    default: &'a Expr<'a>,
+    targets: &'a HashMap<HashableConst<'a>, usize>,
+    branches: &'a [(Expr<'a>, Expr<'a>)],
+) -> Result<Value<'a>> {
+    let expr = expr.row_eval(ctx)?;
+    match as_hash_key(&expr).and_then(|k| targets.get(&k)) {
+        Some(&idx) => branches[idx].1.row_eval(ctx),
+        None => default.row_eval(ctx),
+    }
+}
+
 pub(crate) fn partial_eval_switch_expr<'a, T: ExprEvalContext + 'a>(
     ctx: &'a T,
     args: Vec<(Expr<'a>, Expr<'a>)>,
@@ -155,11 +226,284 @@ pub(crate) fn partial_eval_switch_expr<'a, T: ExprEvalContext + 'a>(
             collected.push((cond, target.partial_eval(ctx)?))
         }
     }
+    if collected.is_empty() {
+        return Ok(default);
+    }
+    if !expr_evaluated {
+        // The match expression is only known at row-eval time: if every branch
+        // condition is a constant we can hash them once here and turn row-eval
+        // into a single map lookup instead of a linear scan.
+        let mut targets = HashMap::with_capacity(collected.len());
+        let mut hashable = true;
+        for (idx, (cond, _)) in collected.iter().enumerate() {
+            match cond {
+                Expr::Const(v) => match as_hash_key(v) {
+                    Some(key) => {
+                        // Keep the first occurrence to preserve first-match semantics.
+                        targets.entry(key).or_insert(idx);
+                    }
+                    None => {
+                        hashable = false;
+                        break;
+                    }
+                },
+                _ => {
+                    hashable = false;
+                    break;
+                }
+            }
+        }
+        if hashable {
+            return Ok(Expr::SwitchExprHash(
+                expr.into(),
+                default.into(),
+                Arc::new(targets),
+                collected,
+            ));
+        }
+    }
+    let mut args = vec![(expr, default)];
+    args.extend(collected);
+    Ok(Expr::SwitchExpr(args))
+}
+
+/// Evaluates a `cond` expression: `args` holds `(predicate, result)` pairs in order,
+/// with the last pair's predicate unused and its result acting as the default.
+pub(crate) fn row_eval_cond<'a, T: RowEvalContext + 'a>(
+    ctx: &'a T,
+    args: &'a [(Expr<'a>, Expr<'a>)],
+) -> Result<Value<'a>> {
+    let (default, branches) = args.split_last().unwrap();
+    for (cond, target) in branches {
+        match cond.row_eval(ctx)? {
+            Value::Bool(true) => return target.row_eval(ctx),
+            Value::Bool(false) | Value::Null => {}
+            v => {
+                return Err(EvalError::OpTypeMismatch(
+                    COND_NAME.to_string(),
+                    vec![v.to_static()],
+                ))
+            }
+        }
+    }
+    default.1.row_eval(ctx)
+}
+
+pub(crate) fn partial_eval_cond<'a, T: ExprEvalContext + 'a>(
+    ctx: &'a T,
+    args: Vec<(Expr<'a>, Expr<'a>)>,
+) -> Result<Expr<'a>> {
+    let mut args = args.into_iter();
+    let (_, mut default) = args.next_back().unwrap();
+    default = default.partial_eval(ctx)?;
+
+    let mut collected = vec![];
+    for (cond, target) in args {
+        let cond = cond.partial_eval(ctx)?;
+        match cond {
+            Expr::Const(Value::Bool(false)) | Expr::Const(Value::Null) => {
+                // cannot match, drop this branch
+            }
+            Expr::Const(Value::Bool(true)) => {
+                default = target.partial_eval(ctx)?;
+                break;
+            }
+            cond => collected.push((cond, target.partial_eval(ctx)?)),
+        }
+    }
+
     if collected.is_empty() {
         Ok(default)
     } else {
-        let mut args = vec![(expr, default)];
-        args.extend(collected);
-        Ok(Expr::SwitchExpr(args))
+        collected.push((Expr::Const(Value::Bool(true)), default));
+        Ok(Expr::CondExpr(collected))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An op with a side effect so that `partial_eval` cannot fold it away, giving
+    /// tests a genuinely non-constant `Expr` to place alongside constant branches.
+    struct NonConstTrue;
+
+    impl Op for NonConstTrue {
+        fn arity(&self) -> Option<usize> {
+            Some(0)
+        }
+        fn has_side_effect(&self) -> bool {
+            true
+        }
+        fn name(&self) -> &str {
+            "++non_const_true++"
+        }
+        fn non_null_args(&self) -> bool {
+            false
+        }
+        fn eval<'a>(&self, _args: Vec<Value<'a>>) -> Result<Value<'a>> {
+            Ok(Value::Bool(true))
+        }
+    }
+
+    /// An op that always errors, used to prove a branch was never evaluated.
+    struct AlwaysErr;
+
+    impl Op for AlwaysErr {
+        fn arity(&self) -> Option<usize> {
+            Some(0)
+        }
+        fn has_side_effect(&self) -> bool {
+            false
+        }
+        fn name(&self) -> &str {
+            "++always_err++"
+        }
+        fn non_null_args(&self) -> bool {
+            false
+        }
+        fn eval<'a>(&self, _args: Vec<Value<'a>>) -> Result<Value<'a>> {
+            Err(EvalError::OpTypeMismatch("++always_err++".to_string(), vec![]))
+        }
+    }
+
+    fn always_err<'a>() -> Expr<'a> {
+        Expr::Apply(Arc::new(AlwaysErr), vec![])
+    }
+
+    #[test]
+    fn row_eval_cond_skips_false_and_null_without_evaluating_them() {
+        let ctx = ();
+        let branches = vec![
+            (Expr::Const(Value::Bool(false)), always_err()),
+            (Expr::Const(Value::Null), always_err()),
+            (Expr::Const(Value::Bool(true)), Expr::Const(Value::Int(42))),
+            (Expr::Const(Value::Bool(true)), always_err()),
+        ];
+        let result = row_eval_cond(&ctx, &branches).unwrap();
+        assert_eq!(result, Value::Int(42));
+    }
+
+    #[test]
+    fn row_eval_cond_falls_through_to_default_without_evaluating_false_branch() {
+        let ctx = ();
+        let branches = vec![
+            (Expr::Const(Value::Bool(false)), always_err()),
+            (Expr::Const(Value::Bool(false)), Expr::Const(Value::Int(7))),
+        ];
+        let result = row_eval_cond(&ctx, &branches).unwrap();
+        assert_eq!(result, Value::Int(7));
+    }
+
+    #[test]
+    fn row_eval_cond_errors_on_non_bool_predicate() {
+        let ctx = ();
+        let branches = vec![
+            (Expr::Const(Value::Int(1)), Expr::Const(Value::Int(1))),
+            (Expr::Const(Value::Bool(true)), Expr::Const(Value::Int(2))),
+        ];
+        assert!(row_eval_cond(&ctx, &branches).is_err());
+    }
+
+    #[test]
+    fn partial_eval_cond_keeps_earlier_branches_when_a_later_predicate_folds_to_true() {
+        // Regression test for the bug fixed in a62e21b: folding the second branch's
+        // predicate to `Const(true)` must not discard the first, non-constant branch.
+        let ctx = ();
+        let branches = vec![
+            (
+                Expr::Apply(Arc::new(NonConstTrue), vec![]),
+                Expr::Const(Value::Int(1)),
+            ),
+            (Expr::Const(Value::Bool(true)), Expr::Const(Value::Int(2))),
+            (Expr::Const(Value::Bool(true)), Expr::Const(Value::Int(3))),
+        ];
+        match partial_eval_cond(&ctx, branches).unwrap() {
+            Expr::CondExpr(kept) => {
+                assert_eq!(kept.len(), 2);
+                assert!(matches!(kept[0].0, Expr::Apply(_, _)));
+                assert_eq!(kept[0].1, Expr::Const(Value::Int(1)));
+                assert_eq!(kept[1].1, Expr::Const(Value::Int(2)));
+            }
+            other => panic!("expected a CondExpr with the first branch preserved, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn as_hash_key_rejects_nan() {
+        assert!(as_hash_key(&Value::Float(f64::NAN)).is_none());
+    }
+
+    #[test]
+    fn as_hash_key_treats_negative_zero_as_zero() {
+        let mut targets = HashMap::new();
+        targets.insert(as_hash_key(&Value::Float(0.0)).unwrap(), 0usize);
+        assert_eq!(
+            targets.get(&as_hash_key(&Value::Float(-0.0)).unwrap()),
+            Some(&0)
+        );
+    }
+
+    #[test]
+    fn duplicate_constant_conditions_keep_first_occurrence() {
+        let mut targets = HashMap::new();
+        for (idx, v) in [Value::Int(1), Value::Int(1), Value::Int(2)]
+            .into_iter()
+            .enumerate()
+        {
+            let key = as_hash_key(&v).unwrap();
+            targets.entry(key).or_insert(idx);
+        }
+        assert_eq!(targets.get(&as_hash_key(&Value::Int(1)).unwrap()), Some(&0));
+        assert_eq!(targets.get(&as_hash_key(&Value::Int(2)).unwrap()), Some(&2));
+    }
+
+    // The two cases below exercise `partial_eval_switch_expr`'s own dispatch decision
+    // (as opposed to the hash-table primitives above), using `NonConstTrue` as a
+    // match expression that partial evaluation cannot fold to a constant.
+    #[test]
+    fn switch_with_nan_condition_falls_back_to_linear_scan() {
+        let ctx = ();
+        let branches = vec![
+            (
+                Expr::Apply(Arc::new(NonConstTrue), vec![]),
+                Expr::Const(Value::Str("default".into())),
+            ),
+            (
+                Expr::Const(Value::Float(f64::NAN)),
+                Expr::Const(Value::Str("nan".into())),
+            ),
+            (
+                Expr::Const(Value::Int(1)),
+                Expr::Const(Value::Str("one".into())),
+            ),
+        ];
+        match partial_eval_switch_expr(&ctx, branches).unwrap() {
+            Expr::SwitchExpr(_) => {}
+            other => panic!("expected a linear SwitchExpr fallback, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn switch_with_all_hashable_conditions_builds_hash_dispatch() {
+        let ctx = ();
+        let branches = vec![
+            (
+                Expr::Apply(Arc::new(NonConstTrue), vec![]),
+                Expr::Const(Value::Str("default".into())),
+            ),
+            (
+                Expr::Const(Value::Int(1)),
+                Expr::Const(Value::Str("one".into())),
+            ),
+            (
+                Expr::Const(Value::Int(2)),
+                Expr::Const(Value::Str("two".into())),
+            ),
+        ];
+        match partial_eval_switch_expr(&ctx, branches).unwrap() {
+            Expr::SwitchExprHash(..) => {}
+            other => panic!("expected a hashed SwitchExprHash, got {other:?}"),
+        }
     }
 }
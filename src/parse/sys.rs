@@ -2,9 +2,12 @@ use itertools::Itertools;
 use miette::{Diagnostic, Result};
 use thiserror::Error;
 
+use crate::data::expr::Expr;
 use crate::data::symb::Symbol;
+use crate::data::value::Value;
+use crate::parse::expr::build_expr;
 use crate::parse::query::parse_query;
-use crate::parse::{ExtractSpan, Pairs, Rule, SourceSpan};
+use crate::parse::{ExtractSpan, Pair, Pairs, Rule, SourceSpan};
 
 #[derive(serde_derive::Serialize, serde_derive::Deserialize)]
 pub(crate) enum SysOp {
@@ -16,7 +19,15 @@ pub(crate) enum SysOp {
     RemoveRelation(Vec<Symbol>),
     RenameRelation(Vec<(Symbol, Symbol)>),
     ShowTrigger(Symbol),
-    SetTriggers(Symbol, Vec<String>, Vec<String>, Vec<String>),
+    SetTriggers(
+        Symbol,
+        Vec<(Option<String>, String)>,
+        Vec<(Option<String>, String)>,
+        Vec<(Option<String>, String)>,
+    ),
+    Explain(String),
+    Backup(String, Vec<Symbol>),
+    Restore(String, Vec<(Symbol, Symbol)>),
 }
 
 #[derive(Debug, Diagnostic, Error)]
@@ -24,6 +35,37 @@ pub(crate) enum SysOp {
 #[diagnostic(code(parser::not_proc_id))]
 struct ProcessIdError(String, #[label] SourceSpan);
 
+#[derive(Debug, Diagnostic, Error)]
+#[error("Backup/restore path must be a string literal")]
+#[diagnostic(code(parser::path_not_string))]
+struct PathNotStringError(#[label] SourceSpan);
+
+/// `::backup`/`::restore` take the target file as a string literal; parse it the
+/// same way any other string-valued expression in the grammar is parsed, rather
+/// than hand-trimming quotes, so escapes are handled correctly.
+fn parse_path_string(pair: Pair<'_>) -> Result<String> {
+    let span = pair.extract_span();
+    match build_expr(pair, &Default::default())? {
+        Expr::Const(Value::Str(s)) => Ok(s.to_string()),
+        _ => Err(PathNotStringError(span).into()),
+    }
+}
+
+/// Parses a sequence of `old -> new` relation name pairs, shared by `::rename` and
+/// `::restore` (the latter renames relations as it loads them).
+fn parse_rename_pairs(pairs: Pairs<'_>) -> Vec<(Symbol, Symbol)> {
+    pairs
+        .map(|pair| {
+            let mut src = pair.into_inner();
+            let rels_p = src.next().unwrap();
+            let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
+            let rels_p = src.next().unwrap();
+            let new_rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
+            (rel, new_rel)
+        })
+        .collect_vec()
+}
+
 pub(crate) fn parse_sys(mut src: Pairs<'_>) -> Result<SysOp> {
     let inner = src.next().unwrap();
     Ok(match inner.as_rule() {
@@ -49,20 +91,7 @@ pub(crate) fn parse_sys(mut src: Pairs<'_>) -> Result<SysOp> {
             let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
             SysOp::ListRelation(rel)
         }
-        Rule::rename_relations_op => {
-            let rename_pairs = inner
-                .into_inner()
-                .map(|pair| {
-                    let mut src = pair.into_inner();
-                    let rels_p = src.next().unwrap();
-                    let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
-                    let rels_p = src.next().unwrap();
-                    let new_rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
-                    (rel, new_rel)
-                })
-                .collect_vec();
-            SysOp::RenameRelation(rename_pairs)
-        }
+        Rule::rename_relations_op => SysOp::RenameRelation(parse_rename_pairs(inner.into_inner())),
         Rule::trigger_relation_show_op => {
             let rels_p = inner.into_inner().next().unwrap();
             let rel = Symbol::new(rels_p.as_str(), rels_p.extract_span());
@@ -78,18 +107,49 @@ pub(crate) fn parse_sys(mut src: Pairs<'_>) -> Result<SysOp> {
             for clause in src {
                 let mut clause_inner = clause.into_inner();
                 let op = clause_inner.next().unwrap();
-                let script = clause_inner.next().unwrap();
-                let script_str = script.as_str();
+                let mut next = clause_inner.next().unwrap();
+                let guard_str = if next.as_rule() == Rule::trigger_guard {
+                    let guard_str = next.as_str().to_string();
+                    let guard_p = next.into_inner().next().unwrap();
+                    build_expr(guard_p, &Default::default())?;
+                    next = clause_inner.next().unwrap();
+                    Some(guard_str)
+                } else {
+                    None
+                };
+                let script = next;
+                let script_str = script.as_str().to_string();
                 parse_query(script.into_inner(), &Default::default())?;
                 match op.as_rule() {
-                    Rule::trigger_put => puts.push(script_str.to_string()),
-                    Rule::trigger_rm => rms.push(script_str.to_string()),
-                    Rule::trigger_replace => replaces.push(script_str.to_string()),
+                    Rule::trigger_put => puts.push((guard_str, script_str)),
+                    Rule::trigger_rm => rms.push((guard_str, script_str)),
+                    Rule::trigger_replace => replaces.push((guard_str, script_str)),
                     r => unreachable!("{:?}", r),
                 }
             }
             SysOp::SetTriggers(rel, puts, rms, replaces)
         }
+        Rule::explain_op => {
+            let script = inner.into_inner().next().unwrap();
+            let script_str = script.as_str().to_string();
+            parse_query(script.into_inner(), &Default::default())?;
+            SysOp::Explain(script_str)
+        }
+        Rule::backup_op => {
+            let mut src = inner.into_inner();
+            let path_p = src.next().unwrap();
+            let path = parse_path_string(path_p)?;
+            let rels = src
+                .map(|rels_p| Symbol::new(rels_p.as_str(), rels_p.extract_span()))
+                .collect_vec();
+            SysOp::Backup(path, rels)
+        }
+        Rule::restore_op => {
+            let mut src = inner.into_inner();
+            let path_p = src.next().unwrap();
+            let path = parse_path_string(path_p)?;
+            SysOp::Restore(path, parse_rename_pairs(src))
+        }
         _ => unreachable!(),
     })
 }